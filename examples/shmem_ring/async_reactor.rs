@@ -0,0 +1,149 @@
+//! Async readiness for a [`Receiver`]/[`Sender`], so a client can be
+//! serviced by a lightweight task on a single-threaded async runtime
+//! instead of a dedicated OS thread.
+//!
+//! The ring signals readiness through an eventfd (`full_signal` for reads,
+//! `empty_signal` for writes); wrapping it in tokio's [`AsyncFd`] lets the
+//! runtime's I/O driver wake the owning task instead of the thread blocking
+//! in `block_until_readable`/`block_until_writable`.
+
+use std::{error::Error, fs::File, io::Read, os::unix::io::AsRawFd};
+
+use shmem_ipc::sharedring::{Receiver, Sender};
+use tokio::io::unix::AsyncFd;
+
+/// `AsyncFd` requires the fd it wraps to already be non-blocking: its
+/// readiness-clearing protocol expects a spurious wakeup to come back with
+/// `WouldBlock`, not to actually block the task (and, on a single-threaded
+/// runtime, every other task sharing it). Same reasoning as the self-pipe
+/// fix in `reactor.rs`.
+fn set_nonblocking(file: &File) -> Result<(), Box<dyn Error>> {
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// A [`Receiver`] whose readiness can be `.await`ed instead of blocked on.
+pub struct AsyncReceiver {
+    inner: Receiver,
+    full_signal: AsyncFd<File>,
+}
+
+impl AsyncReceiver {
+    pub fn new(capacity: usize) -> Result<Self, Box<dyn Error>> {
+        Self::from_receiver(Receiver::new(capacity)?)
+    }
+
+    pub fn from_receiver(inner: Receiver) -> Result<Self, Box<dyn Error>> {
+        let full_signal = inner.full_signal().try_clone()?;
+        set_nonblocking(&full_signal)?;
+        let full_signal = AsyncFd::new(full_signal)?;
+        Ok(Self { inner, full_signal })
+    }
+
+    /// Clones the memfd backing the ring's shared memory, same as
+    /// `Receiver::memfd().as_file()`.
+    pub fn memfd_file(&self) -> Result<File, Box<dyn Error>> {
+        Ok(self.inner.memfd().as_file().try_clone()?)
+    }
+
+    pub fn empty_signal(&self) -> &File {
+        self.inner.empty_signal()
+    }
+
+    pub fn full_signal(&self) -> &File {
+        self.inner.full_signal()
+    }
+
+    /// Resolves once the ring has data available to drain.
+    pub async fn readable(&mut self) -> Result<(), Box<dyn Error>> {
+        loop {
+            let mut guard = self.full_signal.readable_mut().await?;
+            let mut discard = [0u8; 8];
+            match guard.try_io(|fd| fd.get_mut().read(&mut discard)) {
+                Ok(Ok(_)) => return Ok(()),
+                Ok(Err(e)) => return Err(e.into()),
+                // Another task already drained this readiness; go around
+                // and wait for the next one.
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Waits for readiness, then drains whatever is available through `f`,
+    /// same as [`Receiver::receive_raw`].
+    pub async fn receive<T, F>(&mut self, f: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(*const T, usize) -> usize,
+    {
+        self.readable().await?;
+        self.inner.receive_raw(f)?;
+        Ok(())
+    }
+}
+
+/// A [`Sender`] whose readiness can be `.await`ed instead of blocked on, so
+/// writing a slow/stalled client's ring doesn't block the whole
+/// single-threaded runtime out from under every other task.
+pub struct AsyncSender {
+    inner: Sender,
+    empty_signal: AsyncFd<File>,
+}
+
+impl AsyncSender {
+    pub fn new(capacity: usize) -> Result<Self, Box<dyn Error>> {
+        Self::from_sender(Sender::new(capacity)?)
+    }
+
+    pub fn from_sender(inner: Sender) -> Result<Self, Box<dyn Error>> {
+        let empty_signal = inner.empty_signal().try_clone()?;
+        set_nonblocking(&empty_signal)?;
+        let empty_signal = AsyncFd::new(empty_signal)?;
+        Ok(Self { inner, empty_signal })
+    }
+
+    pub fn inner(&self) -> &Sender {
+        &self.inner
+    }
+
+    /// Clones the memfd backing the ring's shared memory, same as
+    /// `Sender::memfd().as_file()`.
+    pub fn memfd_file(&self) -> Result<File, Box<dyn Error>> {
+        Ok(self.inner.memfd().as_file().try_clone()?)
+    }
+
+    pub fn empty_signal(&self) -> &File {
+        self.inner.empty_signal()
+    }
+
+    pub fn full_signal(&self) -> &File {
+        self.inner.full_signal()
+    }
+
+    /// Resolves once the ring has room to write into.
+    pub async fn writable(&mut self) -> Result<(), Box<dyn Error>> {
+        loop {
+            let mut guard = self.empty_signal.readable_mut().await?;
+            let mut discard = [0u8; 8];
+            match guard.try_io(|fd| fd.get_mut().read(&mut discard)) {
+                Ok(Ok(_)) => return Ok(()),
+                Ok(Err(e)) => return Err(e.into()),
+                // Another task already drained this readiness; go around
+                // and wait for the next one.
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Waits for writability, then writes through `f`, same as
+    /// [`Sender::send_raw`].
+    pub async fn send<T, F>(&mut self, f: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(*mut T, usize) -> usize,
+    {
+        self.writable().await?;
+        self.inner.send_raw(f)?;
+        Ok(())
+    }
+}
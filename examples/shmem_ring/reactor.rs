@@ -0,0 +1,191 @@
+//! A single-threaded `poll()` reactor that multiplexes every client's ring
+//! on one thread, instead of spawning an OS thread per ring.
+//!
+//! Each client contributes two fds to the poll set: the ring's
+//! `full_signal` eventfd (readable once there's data to drain) and its
+//! control socket (readable/HUP once the client disconnects). This is also
+//! the natural place to fold in disconnect detection and per-client
+//! accounting, since both signals are already being watched together.
+//!
+//! New clients are registered from other threads (see
+//! [`transport::UnixHandoffServer`](crate::transport::UnixHandoffServer)), so
+//! they arrive over an `mpsc` channel and a self-pipe is used to kick the
+//! reactor out of its blocking `poll()` call to pick them up.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{Read, Write},
+    os::unix::{
+        io::{AsRawFd, FromRawFd, RawFd},
+        net::UnixStream,
+    },
+    sync::{mpsc, Arc, Mutex},
+};
+
+use nix::poll::{poll, PollFd, PollFlags};
+use shmem_ipc::sharedring::Receiver;
+
+use crate::transport::ConnId;
+
+struct Client {
+    receiver: Receiver,
+    control: UnixStream,
+}
+
+struct NewClient {
+    conn_id: ConnId,
+    receiver: Receiver,
+    control: UnixStream,
+}
+
+/// The handle the accept side uses to hand a freshly set-up client over to
+/// the reactor thread.
+#[derive(Clone)]
+pub struct ReactorHandle {
+    new_clients: mpsc::Sender<NewClient>,
+    wake_writer: Arc<std::fs::File>,
+}
+
+impl ReactorHandle {
+    /// Registers `receiver`/`control` with the reactor and wakes it up so it
+    /// starts polling them right away.
+    pub fn register(&self, conn_id: ConnId, receiver: Receiver, control: UnixStream) {
+        // The reactor is blocked in poll() most of the time; if send()
+        // raced a shutdown and the reactor is gone, there's nothing to wake.
+        if self
+            .new_clients
+            .send(NewClient {
+                conn_id,
+                receiver,
+                control,
+            })
+            .is_ok()
+        {
+            // Best-effort: if the reactor is mid-poll it'll see the byte and
+            // loop back around; if it's gone there's nothing to wake.
+            let _ = (&*self.wake_writer).write_all(&[0u8]);
+        }
+    }
+}
+
+/// Owns every accepted client's ring and drains them from a single thread.
+pub struct Reactor {
+    sum: Arc<Mutex<f64>>,
+    clients: HashMap<ConnId, Client>,
+    new_clients: mpsc::Receiver<NewClient>,
+    wake_reader: std::fs::File,
+}
+
+impl Reactor {
+    /// Creates a reactor plus the handle other threads use to feed it new
+    /// clients.
+    pub fn new(sum: Arc<Mutex<f64>>) -> Result<(Self, ReactorHandle), Box<dyn Error>> {
+        let mut pipe_fds = [0 as RawFd; 2];
+        if unsafe { libc::pipe(pipe_fds.as_mut_ptr()) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // The read end is drained in a loop until empty; without
+        // `O_NONBLOCK` that loop blocks on the *next* byte instead of
+        // returning once the pipe is empty, so the reactor would never get
+        // back to poll().
+        if unsafe { libc::fcntl(pipe_fds[0], libc::F_SETFL, libc::O_NONBLOCK) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let wake_reader = unsafe { std::fs::File::from_raw_fd(pipe_fds[0]) };
+        let wake_writer = unsafe { std::fs::File::from_raw_fd(pipe_fds[1]) };
+
+        let (tx, rx) = mpsc::channel();
+        Ok((
+            Self {
+                sum,
+                clients: HashMap::new(),
+                new_clients: rx,
+                wake_reader,
+            },
+            ReactorHandle {
+                new_clients: tx,
+                wake_writer: Arc::new(wake_writer),
+            },
+        ))
+    }
+
+    /// Polls forever, registering newly-accepted clients, draining rings
+    /// that became readable, and reaping clients that disconnected.
+    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        loop {
+            self.poll_once()?;
+        }
+    }
+
+    fn poll_once(&mut self) -> Result<(), Box<dyn Error>> {
+        self.drain_new_clients();
+
+        let ids: Vec<ConnId> = self.clients.keys().copied().collect();
+        let mut fds: Vec<PollFd> = Vec::with_capacity(1 + ids.len() * 2);
+        fds.push(PollFd::new(self.wake_reader.as_raw_fd(), PollFlags::POLLIN));
+        for id in &ids {
+            let client = &self.clients[id];
+            fds.push(PollFd::new(
+                client.receiver.full_signal().as_raw_fd(),
+                PollFlags::POLLIN,
+            ));
+            fds.push(PollFd::new(client.control.as_raw_fd(), PollFlags::POLLIN));
+        }
+
+        poll(&mut fds, -1)?;
+
+        let mut dead = Vec::new();
+        for (i, id) in ids.iter().enumerate() {
+            let ring_events = fds[1 + 2 * i].revents().unwrap_or_else(PollFlags::empty);
+            let control_events = fds[2 + 2 * i].revents().unwrap_or_else(PollFlags::empty);
+
+            // The control socket never carries application traffic after the
+            // handoff, so any readiness on it means the peer went away.
+            if control_events.intersects(PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR) {
+                dead.push(*id);
+                continue;
+            }
+
+            if ring_events.contains(PollFlags::POLLIN) {
+                let sum = self.sum.clone();
+                let client = self.clients.get_mut(id).expect("id came from self.clients");
+                // full_signal is an eventfd: its counter must be read back
+                // to zero or poll() will keep reporting POLLIN forever, even
+                // once there's nothing left to drain.
+                let mut discard = [0u8; 8];
+                let _ = client.receiver.full_signal().read(&mut discard);
+                client.receiver.receive_raw(|ptr: *const f64, count| unsafe {
+                    let mut s = 0.0f64;
+                    for i in 0..count {
+                        s += *ptr.add(i);
+                    }
+                    *sum.lock().unwrap() += s;
+                    count
+                })?;
+            }
+        }
+
+        for id in dead {
+            self.clients.remove(&id);
+        }
+        Ok(())
+    }
+
+    /// Drains the wake pipe and pulls in everything queued on the new-client
+    /// channel since the last poll.
+    fn drain_new_clients(&mut self) {
+        let mut discard = [0u8; 64];
+        while matches!(self.wake_reader.read(&mut discard), Ok(n) if n > 0) {}
+
+        while let Ok(new_client) = self.new_clients.try_recv() {
+            self.clients.insert(
+                new_client.conn_id,
+                Client {
+                    receiver: new_client.receiver,
+                    control: new_client.control,
+                },
+            );
+        }
+    }
+}
@@ -9,47 +9,94 @@ use std::{
     thread,
 };
 
+use tokio::runtime::Handle as TokioHandle;
+
 use dbus::{
     blocking::Connection,
     channel::{MatchingReceiver, Sender},
     Message, MethodErr, Path,
 };
 use dbus_crossroads::Crossroads;
-use shmem_ipc::sharedring::Receiver;
+
+#[path = "reactor.rs"]
+mod reactor;
+use reactor::Reactor;
+
+#[path = "transport.rs"]
+mod transport;
+use transport::UnixHandoffServer;
+
+#[path = "async_reactor.rs"]
+mod async_reactor;
+use async_reactor::AsyncReceiver;
+
+#[path = "rpc.rs"]
+mod rpc;
+use rpc::{HandlerTable, RpcChannel};
+
+// Typed framing on top of the raw ring, used by the RPC path below; the
+// plain f64 receivers still talk to the ring directly since they have
+// nothing to frame.
+#[path = "typed.rs"]
+#[allow(dead_code)]
+mod typed;
 
 const CAPACITY: usize = 500000;
+const UNIX_SOCKET_PATH: &str = "/tmp/shmem-ring-example.sock";
+
+/// Adds a bincode-encoded `f64` to the running sum and replies with the new
+/// total, also bincode-encoded.
+const RPC_METHOD_ADD: rpc::MethodId = 0;
 
-#[derive(Default)]
 struct State {
     sum: Arc<Mutex<f64>>,
+    // Lets the synchronous D-Bus handler spawn the per-client task onto the
+    // runtime driving `AsyncReceiver`, without `State` itself being async.
+    runtime: TokioHandle,
+    rpc_handlers: Arc<HandlerTable>,
 }
 
 impl State {
+    fn new(sum: Arc<Mutex<f64>>, runtime: TokioHandle, rpc_handlers: Arc<HandlerTable>) -> Self {
+        Self {
+            sum,
+            runtime,
+            rpc_handlers,
+        }
+    }
+
+    fn add_rpc_client(&mut self) -> Result<RpcChannel, Box<dyn Error>> {
+        rpc::spawn_rpc_channel(CAPACITY, self.rpc_handlers.clone(), &self.runtime)
+    }
+
     fn add_receiver(&mut self) -> Result<(u64, File, File, File), Box<dyn Error>> {
         // Create a receiver in shared memory.
-        let mut r = Receiver::new(CAPACITY)?;
-        let m = r.memfd().as_file().try_clone()?;
+        let mut r = AsyncReceiver::new(CAPACITY)?;
+        let m = r.memfd_file()?;
         let e = r.empty_signal().try_clone()?;
         let f = r.full_signal().try_clone()?;
-        // In this example, we spawn a thread for every ringbuffer.
-        // More complex real-world scenarios might multiplex using non-block frameworks,
-        // as well as having a mechanism to detect when a client is gone.
+        // Rather than a dedicated OS thread per ringbuffer, the client is
+        // serviced by a lightweight task: the runtime wakes it up only when
+        // the ring's full_signal eventfd becomes readable.
         let sum = self.sum.clone();
-        thread::spawn(move || {
+        self.runtime.spawn(async move {
             loop {
-                r.block_until_readable().unwrap();
                 let mut s = 0.0f64;
-                r.receive_raw(|ptr: *const f64, count| unsafe {
-                    // We now have a slice of [f64; count], but due to the Rust aliasing rules
-                    // and the untrusted process restrictions, we cannot convert them into a
-                    // Rust slice, so we read the data from the raw pointer directly.
-                    for i in 0..count {
-                        s += *ptr.add(i);
-                    }
-                    *sum.lock().unwrap() += s;
-                    count
-                })
-                .unwrap();
+                let result = r
+                    .receive(|ptr: *const f64, count| unsafe {
+                        // We now have a slice of [f64; count], but due to the Rust aliasing rules
+                        // and the untrusted process restrictions, we cannot convert them into a
+                        // Rust slice, so we read the data from the raw pointer directly.
+                        for i in 0..count {
+                            s += *ptr.add(i);
+                        }
+                        *sum.lock().unwrap() += s;
+                        count
+                    })
+                    .await;
+                if result.is_err() {
+                    break;
+                }
             }
         });
         Ok((CAPACITY as u64, m, e, f))
@@ -58,6 +105,42 @@ impl State {
 
 fn main() -> Result<(), Box<dyn Error>> {
     println!("Shmem-ring server is running.");
+    let sum = Arc::new(Mutex::new(0.0f64));
+
+    // D-Bus here stays on its own blocking `Connection::process` loop below;
+    // only the per-client receivers run on this runtime, each as a
+    // lightweight task serviced cooperatively on a single thread instead of
+    // an OS thread per client.
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let rt_handle = rt.handle().clone();
+    // A current-thread runtime only drives spawned tasks while something is
+    // blocked inside it; give it a dedicated OS thread to sit in `block_on`
+    // forever so `rt_handle.spawn(..)` tasks actually get polled.
+    thread::spawn(move || {
+        rt.block_on(std::future::pending::<()>());
+    });
+
+    // Alongside the D-Bus `Setup` call, also accept clients on a unix
+    // socket and hand them a ring over `SCM_RIGHTS`; unlike D-Bus, this lets
+    // us detect when a client disconnects and reclaim its ring. A single
+    // reactor thread polls every such client's ring and control socket at
+    // once, rather than spawning a thread per client.
+    let (mut reactor, reactor_handle) = Reactor::new(sum.clone())?;
+    thread::spawn(move || {
+        if let Err(e) = reactor.run() {
+            println!("reactor stopped: {}", e);
+        }
+    });
+
+    let unix_server = UnixHandoffServer::bind(UNIX_SOCKET_PATH, reactor_handle)?;
+    thread::spawn(move || {
+        if let Err(e) = unix_server.run() {
+            println!("unix handoff server stopped: {}", e);
+        }
+    });
+
     let c = Connection::new_session()?;
     c.request_name("com.example.shmemtest", false, true, false)?;
     let mut cr = Crossroads::new();
@@ -73,9 +156,58 @@ fn main() -> Result<(), Box<dyn Error>> {
                 })
             },
         );
+        b.method(
+            "SetupRpc",
+            (),
+            (
+                "capacity",
+                "request_memfd",
+                "request_empty_signal",
+                "request_full_signal",
+                "response_memfd",
+                "response_empty_signal",
+                "response_full_signal",
+            ),
+            |_, state: &mut State, _: ()| {
+                state
+                    .add_rpc_client()
+                    .map(|c| {
+                        (
+                            c.capacity,
+                            c.request_memfd,
+                            c.request_empty_signal,
+                            c.request_full_signal,
+                            c.response_memfd,
+                            c.response_empty_signal,
+                            c.response_full_signal,
+                        )
+                    })
+                    .map_err(|e| {
+                        println!("{}, {:?}", e, e.source());
+                        MethodErr::failed("failed to setup rpc channel")
+                    })
+            },
+        );
         b.signal::<(f64,), _>("Sum", ("sum",));
     });
-    cr.insert("/shmemtest", &[iface_token], State::default());
+
+    let mut rpc_handlers = HandlerTable::new();
+    let rpc_sum = sum.clone();
+    rpc_handlers.insert(
+        RPC_METHOD_ADD,
+        Box::new(move |payload: &[u8]| {
+            let addend: f64 = bincode::deserialize(payload).unwrap_or(0.0);
+            let mut sum = rpc_sum.lock().unwrap();
+            *sum += addend;
+            bincode::serialize(&*sum).unwrap_or_default()
+        }),
+    );
+
+    cr.insert(
+        "/shmemtest",
+        &[iface_token],
+        State::new(sum.clone(), rt_handle, Arc::new(rpc_handlers)),
+    );
     let acr = Arc::new(Mutex::new(cr));
     let acr_clone = acr.clone();
     c.start_receive(
@@ -0,0 +1,118 @@
+//! Alternative client transport: hand off ring-buffer file descriptors over a
+//! Unix domain socket using `SCM_RIGHTS`, instead of returning them from a
+//! D-Bus method call.
+//!
+//! The D-Bus `Setup` call has no way to notice when a client goes away. A
+//! unix socket gives us that for free: once the peer closes its end, its
+//! control fd becomes readable/HUP, and the [`Reactor`](crate::reactor::Reactor)
+//! tears down the matching ring.
+
+use std::{
+    error::Error,
+    io::IoSlice,
+    os::unix::{
+        io::{AsRawFd, RawFd},
+        net::{UnixListener, UnixStream},
+    },
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use nix::sys::socket::{sendmsg, ControlMessage, MsgFlags};
+use shmem_ipc::sharedring::Receiver;
+
+use crate::{reactor::ReactorHandle, CAPACITY};
+
+/// Identifies one accepted client connection.
+pub type ConnId = u64;
+
+/// Sent as the regular (non-ancillary) payload of the handoff message,
+/// describing the ring whose fds travel alongside it as `SCM_RIGHTS` data.
+#[repr(C)]
+struct Descriptor {
+    capacity: u64,
+    request_id: u64,
+}
+
+/// Accepts clients on a unix socket and hands each one a fresh shared-memory
+/// ring over `SCM_RIGHTS`, registering it with a [`Reactor`] so the ring is
+/// torn down again once the client disconnects.
+pub struct UnixHandoffServer {
+    listener: UnixListener,
+    next_request_id: AtomicU64,
+    next_conn_id: AtomicU64,
+    reactor: ReactorHandle,
+}
+
+impl UnixHandoffServer {
+    pub fn bind(path: &str, reactor: ReactorHandle) -> Result<Self, Box<dyn Error>> {
+        // Binding fails if a stale socket from a previous run is still there.
+        let _ = std::fs::remove_file(path);
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+            next_request_id: AtomicU64::new(0),
+            next_conn_id: AtomicU64::new(0),
+            reactor,
+        })
+    }
+
+    /// Accepts connections forever, registering each client's ring with the
+    /// shared reactor instead of spawning a thread for it. A single failed
+    /// `accept()` or client setup is logged and skipped rather than taking
+    /// down the whole transport, mirroring how the D-Bus `Setup` path turns
+    /// a failure into a `MethodErr` and keeps serving other clients.
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    println!("unix handoff: accept failed: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = self.accept(stream) {
+                println!("unix handoff: failed to set up client: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn accept(&self, stream: UnixStream) -> Result<(), Box<dyn Error>> {
+        let r = Receiver::new(CAPACITY)?;
+        let memfd = r.memfd().as_file().try_clone()?;
+        let empty = r.empty_signal().try_clone()?;
+        let full = r.full_signal().try_clone()?;
+        let request_id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+
+        send_descriptor(
+            &stream,
+            &Descriptor {
+                capacity: CAPACITY as u64,
+                request_id,
+            },
+            &[memfd.as_raw_fd(), empty.as_raw_fd(), full.as_raw_fd()],
+        )?;
+
+        let conn_id = self.next_conn_id.fetch_add(1, Ordering::Relaxed);
+        self.reactor.register(conn_id, r, stream);
+        Ok(())
+    }
+}
+
+/// Sends `descriptor` as the message payload and `fds` as `SCM_RIGHTS`
+/// ancillary data over `stream`.
+fn send_descriptor(
+    stream: &UnixStream,
+    descriptor: &Descriptor,
+    fds: &[RawFd],
+) -> Result<(), Box<dyn Error>> {
+    let bytes = unsafe {
+        std::slice::from_raw_parts(
+            (descriptor as *const Descriptor).cast::<u8>(),
+            std::mem::size_of::<Descriptor>(),
+        )
+    };
+    let iov = [IoSlice::new(bytes)];
+    let cmsg = ControlMessage::ScmRights(fds);
+    sendmsg::<()>(stream.as_raw_fd(), &iov, &[cmsg], MsgFlags::empty(), None)?;
+    Ok(())
+}
@@ -0,0 +1,288 @@
+//! A typed framing layer over the raw ring.
+//!
+//! The plain receivers in `server.rs` read the ring through
+//! [`Receiver::receive_raw`] as a flat array of `f64`s. Here we instead
+//! treat the ring as a byte stream carrying length-prefixed bincode
+//! frames: a native-endian `u32` length header followed by that many bytes
+//! of bincode-encoded payload. This lets the same shared-memory machinery
+//! carry arbitrary structured messages instead of just summable floats.
+
+use std::{error::Error, fmt, marker::PhantomData};
+
+use serde::{de::DeserializeOwned, Serialize};
+use shmem_ipc::sharedring::{Receiver, Sender};
+
+use crate::async_reactor::{AsyncReceiver, AsyncSender};
+
+const LEN_HEADER_SIZE: usize = std::mem::size_of::<u32>();
+
+/// A frame's length prefix claimed more payload than the ring could ever
+/// hold, so it can never become fully readable. Reported instead of
+/// blocking `recv()` forever.
+#[derive(Debug)]
+pub struct OversizedFrame {
+    pub len: usize,
+    pub max_len: usize,
+}
+
+impl fmt::Display for OversizedFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "frame length {} exceeds the ring's maximum payload of {} bytes",
+            self.len, self.max_len
+        )
+    }
+}
+
+impl Error for OversizedFrame {}
+
+/// Tries to decode one length-prefixed frame out of whatever is currently
+/// readable at `ptr`. Shared by the sync and async receivers so the framing
+/// rules only live in one place. Returns the number of bytes to consume
+/// (`0` if a full frame isn't available yet) alongside the decoded value or
+/// error, if any.
+fn decode_frame<T: DeserializeOwned>(
+    ptr: *const u8,
+    available: usize,
+    max_len: usize,
+) -> (usize, Option<T>, Option<Box<dyn Error>>) {
+    if available < LEN_HEADER_SIZE {
+        return (0, None, None);
+    }
+    // Copy the bytes out before touching them further: the ring is
+    // untrusted, aliased memory, so we can't hand out a Rust slice into it
+    // directly.
+    let len = unsafe { ptr.cast::<u32>().read_unaligned() } as usize;
+    if len > max_len {
+        // A frame this large could never fit in the ring, so it can never
+        // become fully readable; fail fast instead of waiting on it forever.
+        return (0, None, Some(Box::new(OversizedFrame { len, max_len })));
+    }
+    let frame_size = LEN_HEADER_SIZE + len;
+    if available < frame_size {
+        return (0, None, None);
+    }
+    let payload = unsafe { std::slice::from_raw_parts(ptr.add(LEN_HEADER_SIZE), len) }.to_vec();
+    match bincode::deserialize(&payload) {
+        Ok(value) => (frame_size, Some(value), None),
+        Err(e) => (frame_size, None, Some(e.into())),
+    }
+}
+
+/// Reads a stream of `T`s framed as `u32`-length-prefixed bincode records
+/// out of a raw byte ring.
+pub struct TypedReceiver<T> {
+    inner: Receiver,
+    capacity: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> TypedReceiver<T> {
+    pub fn new(capacity: usize) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            inner: Receiver::new(capacity)?,
+            capacity,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn from_receiver(inner: Receiver, capacity: usize) -> Self {
+        Self {
+            inner,
+            capacity,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn inner(&self) -> &Receiver {
+        &self.inner
+    }
+
+    /// Blocks until one full frame is available, decodes it, and advances
+    /// the ring past it.
+    pub fn recv(&mut self) -> Result<T, Box<dyn Error>> {
+        loop {
+            self.inner.block_until_readable()?;
+            if let Some(value) = self.try_decode_one()? {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Consumes exactly one frame from whatever is currently readable, or
+    /// consumes nothing and returns `None` if a full frame isn't available
+    /// yet.
+    fn try_decode_one(&mut self) -> Result<Option<T>, Box<dyn Error>> {
+        let max_len = self.capacity.saturating_sub(LEN_HEADER_SIZE);
+        let mut decoded = None;
+        let mut err = None;
+        self.inner.receive_raw(|ptr: *const u8, available| {
+            let (consumed, value, e) = decode_frame(ptr, available, max_len);
+            decoded = value;
+            err = e;
+            consumed
+        })?;
+        if let Some(e) = err {
+            return Err(e);
+        }
+        Ok(decoded)
+    }
+}
+
+/// Same framing as [`TypedReceiver`], but readiness is awaited instead of
+/// blocked on, so it can be driven as a lightweight task on an async
+/// runtime rather than a dedicated OS thread.
+pub struct AsyncTypedReceiver<T> {
+    inner: AsyncReceiver,
+    capacity: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> AsyncTypedReceiver<T> {
+    pub fn new(capacity: usize) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            inner: AsyncReceiver::new(capacity)?,
+            capacity,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn inner(&self) -> &AsyncReceiver {
+        &self.inner
+    }
+
+    /// Waits until one full frame is available, decodes it, and advances
+    /// the ring past it.
+    pub async fn recv(&mut self) -> Result<T, Box<dyn Error>> {
+        let max_len = self.capacity.saturating_sub(LEN_HEADER_SIZE);
+        loop {
+            let mut decoded = None;
+            let mut err = None;
+            self.inner
+                .receive(|ptr: *const u8, available| {
+                    let (consumed, value, e) = decode_frame(ptr, available, max_len);
+                    decoded = value;
+                    err = e;
+                    consumed
+                })
+                .await?;
+            if let Some(e) = err {
+                return Err(e);
+            }
+            if let Some(value) = decoded {
+                return Ok(value);
+            }
+        }
+    }
+}
+
+/// Writes a stream of `T`s framed as `u32`-length-prefixed bincode records
+/// into a raw byte ring.
+pub struct TypedSender<T> {
+    inner: Sender,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Serialize> TypedSender<T> {
+    pub fn new(capacity: usize) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            inner: Sender::new(capacity)?,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn from_sender(inner: Sender) -> Self {
+        Self {
+            inner,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn inner(&self) -> &Sender {
+        &self.inner
+    }
+
+    /// Blocks until there's room, then writes one length-prefixed frame.
+    ///
+    /// `block_until_writable` only guarantees *some* space freed up, not a
+    /// full `frame_size` bytes, so a single attempt can find the ring still
+    /// too full and write nothing; loop until `send_raw`'s closure actually
+    /// reports the whole frame consumed, mirroring the retry loop
+    /// `TypedReceiver::recv` uses on the read side.
+    pub fn send(&mut self, value: &T) -> Result<(), Box<dyn Error>> {
+        let payload = bincode::serialize(value)?;
+        let frame_size = LEN_HEADER_SIZE + payload.len();
+
+        loop {
+            self.inner.block_until_writable()?;
+            let mut written = 0;
+            self.inner.send_raw(|ptr: *mut u8, available| {
+                if available < frame_size {
+                    return 0;
+                }
+                unsafe {
+                    ptr.cast::<u32>().write_unaligned(payload.len() as u32);
+                    std::ptr::copy_nonoverlapping(payload.as_ptr(), ptr.add(LEN_HEADER_SIZE), payload.len());
+                }
+                written = frame_size;
+                frame_size
+            })?;
+            if written == frame_size {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Same framing as [`TypedSender`], but writability is awaited instead of
+/// blocked on, so a slow/stalled client can't stall the whole single-thread
+/// runtime while it still has room to be written into.
+pub struct AsyncTypedSender<T> {
+    inner: AsyncSender,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Serialize> AsyncTypedSender<T> {
+    pub fn new(capacity: usize) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            inner: AsyncSender::new(capacity)?,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn inner(&self) -> &AsyncSender {
+        &self.inner
+    }
+
+    /// Waits until there's room, then writes one length-prefixed frame. See
+    /// [`TypedSender::send`] for why this has to retry.
+    pub async fn send(&mut self, value: &T) -> Result<(), Box<dyn Error>> {
+        let payload = bincode::serialize(value)?;
+        let frame_size = LEN_HEADER_SIZE + payload.len();
+
+        loop {
+            let mut written = 0;
+            self.inner
+                .send(|ptr: *mut u8, available| {
+                    if available < frame_size {
+                        return 0;
+                    }
+                    unsafe {
+                        ptr.cast::<u32>().write_unaligned(payload.len() as u32);
+                        std::ptr::copy_nonoverlapping(
+                            payload.as_ptr(),
+                            ptr.add(LEN_HEADER_SIZE),
+                            payload.len(),
+                        );
+                    }
+                    written = frame_size;
+                    frame_size
+                })
+                .await?;
+            if written == frame_size {
+                return Ok(());
+            }
+        }
+    }
+}